@@ -0,0 +1,10 @@
+//! Thin renames of the raw `openexr-sys` types that are part of this
+//! crate's public surface, kept in one place so the FFI layer can change
+//! without rippling through every module.
+
+use openexr_sys::*;
+
+pub type PixelType = CEXR_PixelType;
+pub type Box2i = CEXR_Box2i;
+pub type V2i = CEXR_V2i;
+pub type Channel = CEXR_Channel;
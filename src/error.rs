@@ -0,0 +1,60 @@
+use std::error::Error as StdError;
+use std::ffi::CStr;
+use std::fmt;
+
+use openexr_sys::*;
+
+/// The error type used throughout this crate.
+///
+/// Wraps the exception text surfaced by OpenEXR's C++ core across the FFI
+/// boundary.
+#[derive(Debug, Clone)]
+pub struct Error(String);
+
+impl Error {
+    /// Builds an `Error` from a raw message returned by the C API.
+    ///
+    /// # Safety
+    /// `msg` must be a valid, NUL-terminated C string.
+    // Not meant to be used outside of this crate, but see the note on
+    // `pub(crate)` in frame_buffer.rs: hide from public docs instead.
+    #[doc(hidden)]
+    pub unsafe fn from_cexr(msg: *const ::libc::c_char) -> Self {
+        Error(CStr::from_ptr(msg).to_string_lossy().into_owned())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        &self.0
+    }
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// Converts a `CEXR_Error` return code plus out-param message into a
+/// `Result`, freeing the message string if one was allocated.
+///
+/// # Safety
+/// `err` and `msg` must come from the same CEXR call, per the
+/// `CEXR_Error`/`*mut *const c_char` out-param convention used by
+/// openexr-sys.
+// Not meant to be used outside of this crate; see the note on
+// `pub(crate)` in frame_buffer.rs.
+#[doc(hidden)]
+pub unsafe fn result(err: CEXR_Error, msg: *const ::libc::c_char) -> Result<()> {
+    match err {
+        CEXR_Error::CEXR_Error_NO_ERROR => Ok(()),
+        _ => {
+            let e = Error::from_cexr(msg);
+            CEXR_free_string(msg as *mut ::libc::c_char);
+            Err(e)
+        }
+    }
+}
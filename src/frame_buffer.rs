@@ -2,11 +2,12 @@ use std::ffi::CString;
 use std::marker::PhantomData;
 use std::mem;
 
+use half::f16;
 use libc::{c_char, c_int};
 
 use openexr_sys::*;
 
-use cexr_type_aliases::*;
+use cexr_type_aliases::{Box2i, PixelType, V2i};
 
 
 /// Types used by OpenEXR to represent a value held by a particular channel at
@@ -27,6 +28,12 @@ unsafe impl ChannelData for f32 {
     }
 }
 
+unsafe impl ChannelData for f16 {
+    fn pixel_type() -> PixelType {
+        PixelType::HALF
+    }
+}
+
 
 // ------------------------------------------------------------------------------
 
@@ -115,6 +122,7 @@ unsafe impl<T: ChannelData> PixelStruct for [T; 4] {
 pub struct FrameBuffer<'a> {
     _handle: *mut CEXR_FrameBuffer,
     _dimensions: (usize, usize),
+    _window: (V2i, V2i),
     _phantom_1: PhantomData<CEXR_FrameBuffer>,
     _phantom_2: PhantomData<&'a mut [u8]>,
 }
@@ -124,6 +132,42 @@ impl<'a> FrameBuffer<'a> {
         FrameBuffer {
             _handle: unsafe { CEXR_FrameBuffer_new() },
             _dimensions: (width, height),
+            _window: (V2i { x: 0, y: 0 },
+                       V2i {
+                           x: width as c_int - 1,
+                           y: height as c_int - 1,
+                       }),
+            _phantom_1: PhantomData,
+            _phantom_2: PhantomData,
+        }
+    }
+
+    /// Creates a `FrameBuffer` backed by a buffer covering only the
+    /// scanlines `[min_y, max_y]` (inclusive) of `data_window`, rather
+    /// than the whole image.
+    ///
+    /// OpenEXR's scanline `readPixels` has no notion of an x sub-range:
+    /// for every scanline it decodes, it always addresses every x across
+    /// the *file's* data window, regardless of how the `FrameBuffer` was
+    /// sized. So `data_window` must be the file's actual, full
+    /// `Header::data_window()` (x range included) — only the y range may
+    /// be narrowed. Passing a `data_window` with a narrower x range than
+    /// the file's will cause OpenEXR to write past the end of the
+    /// (deliberately undersized) buffer.
+    ///
+    /// `min_y`/`max_y` are in the same absolute coordinates as
+    /// `data_window`. Slices later bound with `insert_channel`/
+    /// `insert_pixels` must have length
+    /// `(data_window.max.x - data_window.min.x + 1) * (max_y - min_y + 1)`,
+    /// and `InputFile::read_region` will decode only those scanlines.
+    pub fn with_window(data_window: Box2i, min_y: c_int, max_y: c_int) -> Self {
+        let width = (data_window.max.x - data_window.min.x + 1) as usize;
+        let height = (max_y - min_y + 1) as usize;
+        FrameBuffer {
+            _handle: unsafe { CEXR_FrameBuffer_new() },
+            _dimensions: (width, height),
+            _window: (V2i { x: data_window.min.x, y: min_y },
+                       V2i { x: data_window.max.x, y: max_y }),
             _phantom_1: PhantomData,
             _phantom_2: PhantomData,
         }
@@ -133,6 +177,12 @@ impl<'a> FrameBuffer<'a> {
         self._dimensions
     }
 
+    /// The absolute `(min, max)` pixel coordinates this buffer was
+    /// constructed to cover.
+    pub fn window(&self) -> (V2i, V2i) {
+        self._window
+    }
+
     pub unsafe fn insert_raw(&mut self,
                              name: &str,
                              type_: PixelType,
@@ -156,6 +206,26 @@ impl<'a> FrameBuffer<'a> {
     }
 
     pub fn insert_channel<T: ChannelData>(&mut self, name: &str, fill: f64, data: &'a mut [T]) {
+        self.insert_channel_as(name, T::pixel_type(), fill, data)
+    }
+
+    /// Like [`insert_channel`](#method.insert_channel), but declares the
+    /// channel to OpenEXR as `want` rather than `T::pixel_type()`.
+    ///
+    /// OpenEXR converts between its on-disk channel type and whatever
+    /// type a slice is declared as (HALF, FLOAT and UINT are all
+    /// inter-convertible), so e.g. a HALF-stored channel bound as
+    /// `PixelType::FLOAT` with `T = f32` is read as `f32` values.
+    /// Samples are written into `data` in `want`'s representation, with
+    /// `T`'s size as the stride; no conversion from `want` to `T` is done
+    /// afterwards. A `want` whose representation differs from `T`'s (e.g.
+    /// `HALF` with `T = f32`) leaves `data` holding bit patterns that are
+    /// not valid `T` values.
+    pub fn insert_channel_as<T: ChannelData>(&mut self,
+                                             name: &str,
+                                             want: PixelType,
+                                             fill: f64,
+                                             data: &'a mut [T]) {
         if data.len() != self._dimensions.0 * self._dimensions.1 {
             panic!("data size of {} elements cannot back {}x{} framebuffer",
                    data.len(),
@@ -163,18 +233,61 @@ impl<'a> FrameBuffer<'a> {
                    self._dimensions.1);
         }
         let width = self._dimensions.0;
+        let x_stride = mem::size_of::<T>();
+        let y_stride = width * x_stride;
         unsafe {
-            self.insert_raw(name,
-                            T::pixel_type(),
-                            data.as_mut_ptr() as *mut c_char,
-                            (mem::size_of::<T>(), width * mem::size_of::<T>()),
-                            (1, 1),
-                            fill,
-                            (false, false))
+            let base = self.windowed_base(data.as_mut_ptr() as *mut c_char, x_stride, y_stride);
+            self.insert_raw(name, want, base, (x_stride, y_stride), (1, 1), fill, (false, false))
         };
     }
 
     pub fn insert_pixels<T: PixelStruct>(&mut self, channels: &[(&str, f64)], data: &'a mut [T]) {
+        let channels: Vec<(&str, f64, PixelType)> = channels
+            .iter()
+            .zip(T::channels())
+            .map(|(&(name, fill), (ty, _))| (name, fill, ty))
+            .collect();
+        self.insert_pixels_as(&channels, data)
+    }
+
+    /// Like [`insert_pixels`](#method.insert_pixels), but declares each
+    /// channel's type to OpenEXR independently of the corresponding
+    /// component's natural `ChannelData::pixel_type()`, the same way
+    /// [`insert_channel_as`](#method.insert_channel_as) does for a single
+    /// channel.
+    pub fn insert_pixels_as<T: PixelStruct>(&mut self,
+                                            channels: &[(&str, f64, PixelType)],
+                                            data: &'a mut [T]) {
+        if data.len() != self._dimensions.0 * self._dimensions.1 {
+            panic!("data size of {} elements cannot back {}x{} framebuffer",
+                   data.len(),
+                   self._dimensions.0,
+                   self._dimensions.1);
+        }
+        let width = self._dimensions.0;
+        let x_stride = mem::size_of::<T>();
+        let y_stride = width * x_stride;
+        for (&(name, fill, want), (_, offset)) in channels.iter().zip(T::channels()) {
+            unsafe {
+                let elem_ptr = (data.as_mut_ptr() as *mut c_char).offset(offset as isize);
+                let base = self.windowed_base(elem_ptr, x_stride, y_stride);
+                self.insert_raw(name,
+                                want,
+                                base,
+                                (x_stride, y_stride),
+                                (1, 1),
+                                fill,
+                                (false, false))
+            };
+        }
+    }
+
+    /// Like [`insert_pixels`](#method.insert_pixels), but sets
+    /// `tile_coords = (true, true)` on every inserted channel, for use
+    /// with `TiledInputFile::read_tile`: OpenEXR then addresses the
+    /// buffer relative to the tile's own `(0, 0)` origin rather than the
+    /// image's data window.
+    pub fn insert_pixels_tiled<T: PixelStruct>(&mut self, channels: &[(&str, f64)], data: &'a mut [T]) {
         if data.len() != self._dimensions.0 * self._dimensions.1 {
             panic!("data size of {} elements cannot back {}x{} framebuffer",
                    data.len(),
@@ -182,19 +295,62 @@ impl<'a> FrameBuffer<'a> {
                    self._dimensions.1);
         }
         let width = self._dimensions.0;
+        let x_stride = mem::size_of::<T>();
+        let y_stride = width * x_stride;
         for (&(name, fill), (ty, offset)) in channels.iter().zip(T::channels()) {
             unsafe {
+                let elem_ptr = (data.as_mut_ptr() as *mut c_char).offset(offset as isize);
                 self.insert_raw(name,
                                 ty,
-                                (data.as_mut_ptr() as *mut c_char).offset(offset as isize),
-                                (mem::size_of::<T>(), width * mem::size_of::<T>()),
+                                elem_ptr,
+                                (x_stride, y_stride),
                                 (1, 1),
                                 fill,
-                                (false, false))
+                                (true, true))
             };
         }
     }
 
+    /// Binds a whole layer at once, inserting each of `channels` under
+    /// `prefix.channel` (e.g. `channels = &["R", "G", "B"]` with
+    /// `prefix = "diffuse"` inserts `diffuse.R`, `diffuse.G`, `diffuse.B`).
+    ///
+    /// `prefix` may be `""` to bind unprefixed channels, matching the
+    /// layer names returned by
+    /// [`Header::layers`](struct.Header.html#method.layers). `channels`
+    /// must line up with `T::channels()` the same way
+    /// [`insert_pixels`](#method.insert_pixels) requires.
+    pub fn insert_layer<T: PixelStruct>(&mut self,
+                                        prefix: &str,
+                                        channels: &[&str],
+                                        fill: f64,
+                                        data: &'a mut [T]) {
+        let names: Vec<String> = channels
+            .iter()
+            .map(|c| if prefix.is_empty() {
+                     (*c).to_string()
+                 } else {
+                     format!("{}.{}", prefix, c)
+                 })
+            .collect();
+        let pairs: Vec<(&str, f64)> = names.iter().map(|n| (n.as_str(), fill)).collect();
+        self.insert_pixels(&pairs, data);
+    }
+
+    /// Shifts `data`'s first-element pointer back by `min`'s contribution
+    /// to OpenEXR's absolute `base + x*xStride + y*yStride` addressing, so
+    /// a buffer that only backs the sub-window `[min, max]` still lines up
+    /// with the file's real pixel coordinates.
+    unsafe fn windowed_base(&self,
+                            data_ptr: *mut c_char,
+                            x_stride: usize,
+                            y_stride: usize)
+                            -> *mut c_char {
+        let (min, _) = self._window;
+        let origin_offset = min.y as isize * y_stride as isize + min.x as isize * x_stride as isize;
+        data_ptr.offset(-origin_offset)
+    }
+
     // These shouldn't be used outside of this crate, but due to
     // https://github.com/rust-lang/rfcs/pull/1422 not being stable
     // yet (should land in Rust 1.18), just hide from public
@@ -217,3 +373,36 @@ impl<'a> Drop for FrameBuffer<'a> {
         unsafe { CEXR_FrameBuffer_delete(self._handle) };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use half::f16;
+
+    use super::{ChannelData, PixelStruct};
+    use cexr_type_aliases::PixelType;
+
+    // There's no `OutputFile` in this crate yet, so a true write/read
+    // round trip through an actual EXR file isn't possible to test here.
+    // These instead pin down the layout guarantees `insert_channel`/
+    // `insert_pixels` rely on for HALF data: the declared `PixelType` and
+    // the byte offsets `PixelStruct` computes for `f16`-sized channels.
+
+    #[test]
+    fn f16_pixel_type_is_half() {
+        assert_eq!(f16::pixel_type(), PixelType::HALF);
+    }
+
+    #[test]
+    fn f16_array_channel_offsets_use_two_byte_stride() {
+        assert_eq!(<[f16; 3]>::channel_count(), 3);
+        assert_eq!(<[f16; 3]>::channel(0), (PixelType::HALF, 0));
+        assert_eq!(<[f16; 3]>::channel(1), (PixelType::HALF, 2));
+        assert_eq!(<[f16; 3]>::channel(2), (PixelType::HALF, 4));
+    }
+
+    #[test]
+    fn mixed_struct_channel_offsets_account_for_f16_size() {
+        assert_eq!(<(f16, f32)>::channel(0), (PixelType::HALF, 0));
+        assert_eq!(<(f16, f32)>::channel(1), (PixelType::FLOAT, 2));
+    }
+}
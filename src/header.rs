@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::marker::PhantomData;
+use std::mem;
+
+use openexr_sys::*;
+
+use cexr_type_aliases::*;
+
+/// Metadata describing an OpenEXR image: its data window, channel list,
+/// and other attributes.
+///
+/// A `Header` is always borrowed from an `InputFile` or a
+/// `TiledInputFile` (or, in future, an `OutputFile`); it never outlives
+/// the file it describes.
+pub struct Header<'a> {
+    _handle: *const CEXR_Header,
+    _phantom: PhantomData<&'a CEXR_Header>,
+}
+
+impl<'a> Header<'a> {
+    /// Wraps a handle owned by an input/output file.
+    ///
+    /// # Safety
+    /// `handle` must remain valid for the lifetime `'a`.
+    // Not meant to be used outside of this crate; see the note on
+    // `pub(crate)` in frame_buffer.rs.
+    #[doc(hidden)]
+    pub unsafe fn new(handle: *const CEXR_Header) -> Self {
+        Header {
+            _handle: handle,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The window of pixels actually stored in the file.
+    pub fn data_window(&self) -> Box2i {
+        unsafe { CEXR_Header_data_window(self._handle) }
+    }
+
+    /// The window of pixels the image is intended to be displayed within.
+    pub fn display_window(&self) -> Box2i {
+        unsafe { CEXR_Header_display_window(self._handle) }
+    }
+
+    /// Looks up a channel by its fully-qualified name (e.g. `"R"` or, for
+    /// a layered image, `"diffuse.R"`).
+    pub fn get_channel(&self, name: &str) -> Option<Channel> {
+        let c_name = CString::new(name).unwrap();
+        unsafe {
+            let mut channel: Channel = mem::zeroed();
+            let found = CEXR_Header_get_channel(self._handle, c_name.as_ptr(), &mut channel);
+            if found != 0 { Some(channel) } else { None }
+        }
+    }
+
+    /// Iterates over every channel stored in the file, in file order.
+    pub fn channels(&self) -> ChannelIter {
+        ChannelIter {
+            header: self._handle,
+            index: 0,
+            count: unsafe { CEXR_Header_num_channels(self._handle) },
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Groups this file's channels by the `.`-separated layer convention
+    /// (e.g. `diffuse.R`/`diffuse.G` belong to layer `"diffuse"`).
+    ///
+    /// Channels with no `.` in their name (ordinary `R`/`G`/`B`/`A` on an
+    /// unlayered image) are grouped under the layer named `""`. Layers
+    /// are returned sorted by layer name, so the `""` layer, if present,
+    /// comes first.
+    pub fn layers(&self) -> Vec<Layer> {
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (name, _channel) in self.channels() {
+            match name.rfind('.') {
+                Some(pos) => {
+                    groups
+                        .entry(name[..pos].to_string())
+                        .or_insert_with(Vec::new)
+                        .push(name[pos + 1..].to_string());
+                }
+                None => {
+                    groups
+                        .entry(String::new())
+                        .or_insert_with(Vec::new)
+                        .push(name);
+                }
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(name, channels)| Layer { name, channels })
+            .collect()
+    }
+
+    #[doc(hidden)]
+    pub fn handle(&self) -> *const CEXR_Header {
+        self._handle
+    }
+}
+
+/// A group of channels sharing a common `layer.` name prefix, as returned
+/// by [`Header::layers`](struct.Header.html#method.layers).
+#[derive(Debug, Clone)]
+pub struct Layer {
+    /// The layer's name, or `""` for channels with no `.`-separated
+    /// prefix.
+    pub name: String,
+    /// The part of each channel's name after the `.` (e.g. `"R"`), in
+    /// file order.
+    pub channels: Vec<String>,
+}
+
+/// Iterator over a `Header`'s channels, yielding `(name, Channel)` pairs.
+///
+/// Created by [`Header::channels`](struct.Header.html#method.channels).
+pub struct ChannelIter<'a> {
+    header: *const CEXR_Header,
+    index: usize,
+    count: usize,
+    _phantom: PhantomData<&'a CEXR_Header>,
+}
+
+impl<'a> Iterator for ChannelIter<'a> {
+    type Item = (String, Channel);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.count {
+            return None;
+        }
+        let (name, channel) = unsafe {
+            let c_name = CEXR_Header_channel_name(self.header, self.index);
+            let mut channel: Channel = mem::zeroed();
+            CEXR_Header_channel_at(self.header, self.index, &mut channel);
+            (CStr::from_ptr(c_name).to_string_lossy().into_owned(), channel)
+        };
+        self.index += 1;
+        Some((name, channel))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.count - self.index;
+        (remaining, Some(remaining))
+    }
+}
@@ -0,0 +1,118 @@
+use std::ffi::CString;
+use std::path::Path;
+use std::ptr;
+
+use openexr_sys::*;
+
+use error::{self, Result};
+use frame_buffer::FrameBuffer;
+use header::Header;
+
+/// A handle to an OpenEXR scanline image opened for reading.
+pub struct InputFile {
+    _handle: *mut CEXR_InputFile,
+}
+
+impl InputFile {
+    /// Opens the scanline EXR file at `path`.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let mut error_out = ptr::null();
+        let mut handle = ptr::null_mut();
+        let err = unsafe {
+            CEXR_InputFile_from_file(c_path.as_ptr(), &mut handle, &mut error_out)
+        };
+        unsafe { error::result(err, error_out)? };
+        Ok(InputFile { _handle: handle })
+    }
+
+    /// The header describing this file's channels and windows.
+    pub fn header(&self) -> Header {
+        unsafe { Header::new(CEXR_InputFile_header(self._handle)) }
+    }
+
+    /// Reads the whole data window into `framebuffer` on the calling
+    /// thread.
+    pub fn read_pixels(&self, framebuffer: &mut FrameBuffer) -> Result<()> {
+        let window = self.header().data_window();
+        self.read_pixels_range(framebuffer, window.min.y, window.max.y)
+    }
+
+    /// Reads only the scanlines `framebuffer` was constructed to cover
+    /// (see [`FrameBuffer::with_window`](struct.FrameBuffer.html#method.with_window)),
+    /// rather than the whole data window.
+    ///
+    /// This lets callers stream a large image in horizontal strips
+    /// without ever allocating a buffer for the full frame. Note that
+    /// `with_window` only allows narrowing the y range — OpenEXR's
+    /// scanline reader always addresses the full data window's x range,
+    /// so there is no way to crop horizontally without decoding full-width
+    /// scanlines and clipping in Rust afterwards.
+    pub fn read_region(&self, framebuffer: &mut FrameBuffer) -> Result<()> {
+        let (min, max) = framebuffer.window();
+        self.read_pixels_range(framebuffer, min.y, max.y)
+    }
+
+    /// Like [`read_pixels`](#method.read_pixels), but decodes compressed
+    /// blocks across OpenEXR's global thread pool (see
+    /// [`set_thread_count`](fn.set_thread_count.html)).
+    ///
+    /// The calling thread still drives the read and blocks until every
+    /// worker has finished, but independent scanline blocks are
+    /// decompressed concurrently, which is a substantial win for
+    /// ZIP/PIZ-compressed images with more than a handful of scanlines
+    /// per block.
+    pub fn read_pixels_parallel(&self, framebuffer: &mut FrameBuffer) -> Result<()> {
+        let window = self.header().data_window();
+        let mut error_out = ptr::null();
+        let err = unsafe {
+            CEXR_InputFile_read_pixels_parallel(
+                self._handle,
+                framebuffer.handle_mut(),
+                window.min.y,
+                window.max.y,
+                &mut error_out,
+            )
+        };
+        unsafe { error::result(err, error_out) }
+    }
+
+    fn read_pixels_range(
+        &self,
+        framebuffer: &mut FrameBuffer,
+        scanline_min: i32,
+        scanline_max: i32,
+    ) -> Result<()> {
+        let mut error_out = ptr::null();
+        let err = unsafe {
+            CEXR_InputFile_read_pixels(
+                self._handle,
+                framebuffer.handle_mut(),
+                scanline_min,
+                scanline_max,
+                &mut error_out,
+            )
+        };
+        unsafe { error::result(err, error_out) }
+    }
+}
+
+impl Drop for InputFile {
+    fn drop(&mut self) {
+        unsafe { CEXR_InputFile_delete(self._handle) };
+    }
+}
+
+unsafe impl Send for InputFile {}
+unsafe impl Sync for InputFile {}
+
+/// Sets the size of the global thread pool OpenEXR's C++ core uses for
+/// parallel compression and decompression (`Imf::setGlobalThreadCount`).
+///
+/// A value of `0` disables threading and decodes everything on the
+/// calling thread, which is also the default. This affects every
+/// `InputFile`/`OutputFile` in the process, not just ones created after
+/// the call.
+pub fn set_thread_count(n: usize) {
+    unsafe { CEXR_setGlobalThreadCount(n as ::libc::c_int) };
+}
@@ -0,0 +1,17 @@
+extern crate half;
+extern crate libc;
+extern crate openexr_sys;
+
+mod cexr_type_aliases;
+mod error;
+mod frame_buffer;
+mod header;
+mod input;
+mod tiled_input;
+
+pub use cexr_type_aliases::*;
+pub use error::{Error, Result};
+pub use frame_buffer::{ChannelData, FrameBuffer, PixelStruct};
+pub use header::{ChannelIter, Header, Layer};
+pub use input::{set_thread_count, InputFile};
+pub use tiled_input::{TileCache, TiledInputFile};
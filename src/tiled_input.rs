@@ -0,0 +1,307 @@
+use std::collections::{HashMap, VecDeque};
+use std::ffi::CString;
+use std::mem;
+use std::path::Path;
+use std::ptr;
+
+use openexr_sys::*;
+
+use error::{self, Result};
+use frame_buffer::{FrameBuffer, PixelStruct};
+use header::Header;
+
+/// A handle to an OpenEXR tiled (and optionally mip-/rip-mapped) image
+/// opened for reading.
+///
+/// Unlike `InputFile`, tiles can be decoded individually and in any
+/// order via [`read_tile`](#method.read_tile), which is what makes
+/// texture-streaming-style access practical; see [`TileCache`] for a
+/// bounded cache built on top of that.
+pub struct TiledInputFile {
+    _handle: *mut CEXR_TiledInputFile,
+}
+
+impl TiledInputFile {
+    /// Opens the tiled EXR file at `path`.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let mut error_out = ptr::null();
+        let mut handle = ptr::null_mut();
+        let err = unsafe {
+            CEXR_TiledInputFile_from_file(c_path.as_ptr(), &mut handle, &mut error_out)
+        };
+        unsafe { error::result(err, error_out)? };
+        Ok(TiledInputFile { _handle: handle })
+    }
+
+    /// The header describing this file's channels and windows.
+    pub fn header(&self) -> Header {
+        unsafe { Header::new(CEXR_TiledInputFile_header(self._handle)) }
+    }
+
+    /// The size, in pixels, of a single tile (the last tile in each row
+    /// or column of a level may be smaller, where the level isn't an
+    /// even multiple of this).
+    pub fn tile_dimensions(&self) -> (usize, usize) {
+        unsafe {
+            let mut w = 0;
+            let mut h = 0;
+            CEXR_TiledInputFile_tile_dimensions(self._handle, &mut w, &mut h);
+            (w, h)
+        }
+    }
+
+    /// The number of levels in the x and y mip/rip axes. For an ordinary
+    /// mip-mapped file these are equal; for a one-level (non-mip-mapped)
+    /// tiled file both are `1`.
+    pub fn level_count(&self) -> (usize, usize) {
+        unsafe {
+            let mut x = 0;
+            let mut y = 0;
+            CEXR_TiledInputFile_level_count(self._handle, &mut x, &mut y);
+            (x, y)
+        }
+    }
+
+    /// The number of tiles along x and y at level `(level_x, level_y)`.
+    pub fn tile_count(&self, level_x: usize, level_y: usize) -> (usize, usize) {
+        unsafe {
+            let mut x = 0;
+            let mut y = 0;
+            CEXR_TiledInputFile_tile_count(self._handle, level_x, level_y, &mut x, &mut y);
+            (x, y)
+        }
+    }
+
+    /// Decodes a single tile into `framebuffer`, which must be at least as
+    /// large as the tile being read. A buffer sized to
+    /// [`tile_dimensions`](#method.tile_dimensions) fits every tile; the
+    /// tiles in the last row/column of a level may be smaller, and then
+    /// fill only the top-left part of it.
+    ///
+    /// Channels must be bound with `tile_coords = (true, true)` (see
+    /// [`FrameBuffer::insert_raw`](struct.FrameBuffer.html#method.insert_raw)),
+    /// which tells OpenEXR to address the buffer relative to the tile's
+    /// own origin rather than the image's data window.
+    pub fn read_tile(&self,
+                     framebuffer: &mut FrameBuffer,
+                     tile_x: usize,
+                     tile_y: usize,
+                     level_x: usize,
+                     level_y: usize)
+                     -> Result<()> {
+        let mut error_out = ptr::null();
+        let err = unsafe {
+            CEXR_TiledInputFile_read_tile(self._handle,
+                                          framebuffer.handle_mut(),
+                                          tile_x,
+                                          tile_y,
+                                          level_x,
+                                          level_y,
+                                          &mut error_out)
+        };
+        unsafe { error::result(err, error_out) }
+    }
+}
+
+impl Drop for TiledInputFile {
+    fn drop(&mut self) {
+        unsafe { CEXR_TiledInputFile_delete(self._handle) };
+    }
+}
+
+unsafe impl Send for TiledInputFile {}
+unsafe impl Sync for TiledInputFile {}
+
+type TileKey = (usize, usize, usize, usize);
+
+/// Pure LRU bookkeeping for a byte-budgeted tile cache: which keys are
+/// held, in what recency order, and how many bytes that costs.
+///
+/// Kept free of any FFI/decoding concerns so it can be unit tested on
+/// its own; [`TileCache`](struct.TileCache.html) is the thin layer that
+/// calls into `TiledInputFile::read_tile` to actually produce the `Vec<T>`
+/// this stores.
+struct LruTileStore<T> {
+    budget_bytes: usize,
+    used_bytes: usize,
+    tiles: HashMap<TileKey, Vec<T>>,
+    // Least-recently-used key is at the front; eviction pops from there.
+    // Every key in `recency` appears exactly once and matches a key in
+    // `tiles`, and vice versa.
+    recency: VecDeque<TileKey>,
+}
+
+impl<T> LruTileStore<T> {
+    fn new(budget_bytes: usize) -> Self {
+        LruTileStore {
+            budget_bytes,
+            used_bytes: 0,
+            tiles: HashMap::new(),
+            recency: VecDeque::new(),
+        }
+    }
+
+    fn get(&self, key: &TileKey) -> Option<&[T]> {
+        self.tiles.get(key).map(|data| data.as_slice())
+    }
+
+    fn contains(&self, key: &TileKey) -> bool {
+        self.tiles.contains_key(key)
+    }
+
+    /// Moves `key` to most-recently-used, without affecting what's
+    /// cached. No-op if `key` isn't present.
+    fn touch(&mut self, key: TileKey) {
+        if let Some(pos) = self.recency.iter().position(|&k| k == key) {
+            self.recency.remove(pos);
+            self.recency.push_back(key);
+        }
+    }
+
+    /// Inserts a freshly-decoded tile as most-recently-used, then evicts
+    /// least-recently-used tiles until back under `budget_bytes` — except
+    /// `key` itself is never evicted, so a single tile whose own size
+    /// exceeds the budget is still returned to the caller rather than
+    /// causing a panic or an unbounded eviction loop.
+    fn insert(&mut self, key: TileKey, data: Vec<T>) {
+        self.used_bytes += data.len() * mem::size_of::<T>();
+        self.tiles.insert(key, data);
+        self.recency.push_back(key);
+        self.evict_except(key);
+    }
+
+    fn evict_except(&mut self, protect: TileKey) {
+        while self.used_bytes > self.budget_bytes {
+            let lru = match self.recency.front().cloned() {
+                Some(k) => k,
+                None => break,
+            };
+            if lru == protect {
+                break;
+            }
+            self.recency.pop_front();
+            if let Some(data) = self.tiles.remove(&lru) {
+                self.used_bytes -= data.len() * mem::size_of::<T>();
+            }
+        }
+    }
+}
+
+/// A bounded, least-recently-used cache of decoded tiles from a
+/// `TiledInputFile`, for viewport- or texture-streaming-style access to
+/// gigapixel images without ever decoding the whole thing.
+///
+/// Every cached tile is bound to the same fixed set of `channels` and
+/// pixel layout `T`, chosen up front in [`new`](#method.new).
+pub struct TileCache<T: PixelStruct + Default + Clone> {
+    file: TiledInputFile,
+    channels: Vec<(String, f64)>,
+    store: LruTileStore<T>,
+}
+
+impl<T: PixelStruct + Default + Clone> TileCache<T> {
+    /// Creates a cache over `file` that will decode `channels` (the same
+    /// `(name, fill)` pairs `FrameBuffer::insert_pixels` takes) on
+    /// demand, evicting least-recently-used tiles once more than
+    /// `budget_bytes` of decoded pixel data is held.
+    pub fn new(file: TiledInputFile, channels: Vec<(String, f64)>, budget_bytes: usize) -> Self {
+        TileCache {
+            file,
+            channels,
+            store: LruTileStore::new(budget_bytes),
+        }
+    }
+
+    /// Returns the decoded pixels for the given tile, decoding and
+    /// caching it first if necessary.
+    pub fn get_tile(&mut self,
+                    tile_x: usize,
+                    tile_y: usize,
+                    level_x: usize,
+                    level_y: usize)
+                    -> Result<&[T]> {
+        let key = (tile_x, tile_y, level_x, level_y);
+        if self.store.contains(&key) {
+            self.store.touch(key);
+        } else {
+            let data = self.decode(key)?;
+            self.store.insert(key, data);
+        }
+        Ok(self.store.get(&key).expect("just inserted or already present"))
+    }
+
+    fn decode(&self, key: TileKey) -> Result<Vec<T>> {
+        let (tile_x, tile_y, level_x, level_y) = key;
+        let (tile_w, tile_h) = self.file.tile_dimensions();
+        let (tiles_x, tiles_y) = self.file.tile_count(level_x, level_y);
+        if tile_x >= tiles_x || tile_y >= tiles_y {
+            panic!("tile ({}, {}) out of range for level ({}, {})",
+                   tile_x,
+                   tile_y,
+                   level_x,
+                   level_y);
+        }
+        let mut data = vec![T::default(); tile_w * tile_h];
+        {
+            let names: Vec<(&str, f64)> = self.channels
+                .iter()
+                .map(|&(ref name, fill)| (name.as_str(), fill))
+                .collect();
+            let mut fb = FrameBuffer::new(tile_w, tile_h);
+            fb.insert_pixels_tiled(&names, &mut data);
+            self.file.read_tile(&mut fb, tile_x, tile_y, level_x, level_y)?;
+        }
+        Ok(data)
+    }
+
+    /// The number of decoded-tile bytes currently held.
+    pub fn used_bytes(&self) -> usize {
+        self.store.used_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LruTileStore;
+
+    fn key(i: usize) -> (usize, usize, usize, usize) {
+        (i, 0, 0, 0)
+    }
+
+    #[test]
+    fn single_oversized_tile_is_kept_not_evicted() {
+        let mut store = LruTileStore::new(10);
+        store.insert(key(0), vec![0u8; 100]);
+        assert!(store.contains(&key(0)));
+        assert_eq!(store.get(&key(0)).unwrap().len(), 100);
+        assert_eq!(store.used_bytes, 100);
+    }
+
+    #[test]
+    fn inserting_over_budget_evicts_least_recently_used() {
+        let mut store = LruTileStore::new(20);
+        store.insert(key(0), vec![0u8; 10]);
+        store.insert(key(1), vec![0u8; 10]);
+        // Exactly at budget now; inserting a third tile pushes it over and
+        // should evict key(0), the least recently used, not key(1).
+        store.insert(key(2), vec![0u8; 10]);
+        assert!(!store.contains(&key(0)));
+        assert!(store.contains(&key(1)));
+        assert!(store.contains(&key(2)));
+    }
+
+    #[test]
+    fn touch_protects_a_key_from_the_next_eviction() {
+        let mut store = LruTileStore::new(20);
+        store.insert(key(0), vec![0u8; 10]);
+        store.insert(key(1), vec![0u8; 10]);
+        store.touch(key(0));
+        // key(0) is now most-recently-used, so key(1) should be evicted
+        // instead when a third tile pushes the store over budget.
+        store.insert(key(2), vec![0u8; 10]);
+        assert!(store.contains(&key(0)));
+        assert!(!store.contains(&key(1)));
+        assert!(store.contains(&key(2)));
+    }
+}